@@ -2,15 +2,19 @@ mod network;
 mod utils;
 
 use clap::{Arg, Command};
-use rand::Rng; 
+use network::identity::Identity;
+use network::peer::{Destination, NodeHandle};
+use network::transport::{Listener, NamedSocketAddr};
+use network::view::{PeerView, GOSSIP_FANOUT};
+use rand::Rng;
+use rand::RngCore;
 use rand::SeedableRng;
-use std::net::SocketAddr;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::net::TcpListener;
 use tokio::sync::broadcast;
 
-type SharedPeers = Arc<Mutex<std::collections::HashSet<SocketAddr>>>;
+type SharedPeers = Arc<Mutex<PeerView>>;
 
 #[tokio::main]
 async fn main() {
@@ -23,36 +27,83 @@ async fn main() {
         .arg(Arg::new("port")
             .long("port")
             .required(true)
-            .value_name("PORT")
-            .help("Set the port number"))
+            .value_name("ADDRESS")
+            .help("Address to listen on: a port number, a host:port, or a filesystem path for a Unix domain socket"))
         .arg(Arg::new("connect")
             .long("connect")
             .value_name("ADDRESS")
-            .help("Connect to a peer at ADDRESS"))
+            .help("Connect to a peer at ADDRESS: a host:port or a filesystem path for a Unix domain socket"))
+        .arg(Arg::new("network-key")
+            .long("network-key")
+            .value_name("KEY")
+            .help("Shared cluster admission key, hex or base62 encoded (generated and printed if omitted)"))
+        .arg(Arg::new("private-key")
+            .long("private-key")
+            .value_name("KEY")
+            .help("This node's ed25519 identity secret key, hex or base62 encoded (generated if omitted)"))
         .get_matches();
 
     let period: u64 = matches.get_one::<String>("period").unwrap()
         .parse::<u64>().expect("Invalid period");
-    let port: u16 = matches.get_one::<String>("port").unwrap()
-        .parse::<u16>().expect("Invalid port");
-    let connect_addr = matches.get_one::<String>("connect");
-
-    let addr = format!("127.0.0.1:{}", port).parse().unwrap();
-    let listener = TcpListener::bind(&addr).await.unwrap();
+    let listen_addr = NamedSocketAddr::parse(matches.get_one::<String>("port").unwrap());
+    let connect_addr = matches.get_one::<String>("connect").map(|s| NamedSocketAddr::parse(s));
+
+    let identity = Arc::new(match matches.get_one::<String>("private-key") {
+        Some(key) => Identity::from_encoded(key).expect("Invalid --private-key"),
+        None => {
+            let identity = Identity::generate();
+            eprintln!(
+                "No --private-key supplied; generated identity {}. Pass --private-key {} to reuse it across restarts.",
+                identity.node_id,
+                identity.to_encoded()
+            );
+            identity
+        }
+    });
 
-    let start_time = Instant::now();
+    let network_key = Arc::new(match matches.get_one::<String>("network-key") {
+        Some(key) => network::encoding::decode_key_material(key, 32).expect("Invalid --network-key"),
+        None => {
+            let mut key = vec![0u8; 32];
+            rand::rngs::OsRng.fill_bytes(&mut key);
+            eprintln!(
+                "No --network-key supplied; generated one. Pass --network-key {} to every peer that should join this cluster.",
+                network::encoding::encode_hex(&key)
+            );
+            key
+        }
+    });
 
-    utils::log_with_timestamp(start_time, &format!("My address is \"{}\"", addr));
+    let listener = Listener::bind(&listen_addr).await.unwrap();
 
-    let peers: SharedPeers = Arc::new(Mutex::new(std::collections::HashSet::new()));
-    let (tx, _) = broadcast::channel(16);
+    let start_time = Instant::now();
+    let self_id = identity.node_id;
+
+    utils::log_with_timestamp(start_time, &format!("My address is \"{}\", node id {}", listen_addr, self_id));
+
+    let peers: SharedPeers = Arc::new(Mutex::new(PeerView::new()));
+    let seen: network::peer::SharedSeen = Arc::new(Mutex::new(network::seen::SeenMessages::new(network::seen::SEEN_CAPACITY)));
+    let stats: network::stats::SharedStats = Arc::new(Mutex::new(HashMap::new()));
+    let (tx, _) = broadcast::channel::<(network::message::NetworkData, Destination)>(16);
+
+    let node = NodeHandle {
+        identity: identity.clone(),
+        network_key: network_key.clone(),
+        listen_addr,
+        peers: peers.clone(),
+        seen: seen.clone(),
+        stats: stats.clone(),
+        tx: tx.clone(),
+        start_time,
+    };
 
     if let Some(connect_addr) = connect_addr {
-        let connect_addr: SocketAddr = connect_addr.parse().unwrap();
-        network::peer::connect_to_peer(connect_addr, port, peers.clone(), tx.clone(), addr, start_time).await;
+        network::peer::connect_to_peer(connect_addr, node.clone()).await;
     }
 
-    tokio::spawn(network::peer::accept_connections(listener, peers.clone(), tx.clone(), addr, start_time));
+    tokio::spawn(network::peer::accept_connections(listener, node.clone()));
+    tokio::spawn(network::reconnect::run_reconnect_supervisor(node.clone()));
+    tokio::spawn(network::stats::run_stats_reporter(stats, start_time));
 
     let peers_clone = peers.clone();
     let tx_clone = tx.clone();
@@ -63,41 +114,34 @@ async fn main() {
         loop {
             interval.tick().await;
             let message = network::message::Message {
+                id: rng.gen::<u128>(),
                 content: rng.gen::<u32>().to_string(),
-                from: addr,
+                from: self_id,
                 timestamp: utils::current_timestamp(),
+                ttl: network::message::INITIAL_TTL,
             };
-            let network_data = network::message::NetworkData::Message(message);
-            let message_json = serde_json::to_string(&network_data).unwrap() + "\n"; // Add a delimiter
-            let peers = peers_clone.lock().unwrap().clone();
+            let network_data = network::message::NetworkData::Message(Box::new(message));
 
+            // Disseminate to a random fanout subset of the view rather than everyone
+            let fanout = peers_clone.lock().unwrap().random_subset(GOSSIP_FANOUT);
             utils::log_with_timestamp(start_time, &format!(
                 "Sending message [{}] to {:?}",
-                if let network::message::NetworkData::Message(ref msg) = network_data { &msg.content } else { "" }, peers
+                if let network::message::NetworkData::Message(ref msg) = network_data { &msg.content } else { "" },
+                fanout
             ));
-
-            for peer in &peers {
-                if peer != &addr {
-                    let _ = tx_clone.send((message_json.clone(), *peer));
-                }
+            for peer_id in &fanout {
+                let _ = tx_clone.send((network_data.clone(), Destination::Direct(*peer_id)));
             }
 
-            // Share known peers excluding self
-            let known_peers: Vec<SocketAddr> = peers_clone.lock().unwrap()
-                .iter().cloned().filter(|p| p != &addr).collect();
-            for peer in &peers {
-                if peer != &addr {
-                    let peer_info = network::message::PeerInfo { port: addr.port(), known_peers: known_peers.clone() };
-                    let network_data = network::message::NetworkData::PeerInfo(peer_info);
-                    let peer_info_json = serde_json::to_string(&network_data).unwrap() + "\n";
-                    let _ = tx_clone.send((peer_info_json, *peer));
-                }
+            // Peer sampling: pull a random peer's view to refresh and heal our own
+            if let Some(target) = peers_clone.lock().unwrap().random_peer() {
+                let _ = tx_clone.send((network::message::NetworkData::Pull, Destination::Direct(target)));
             }
         }
     });
 
     // Show received messages in a separate task to allow for reset of the subscription
-    tokio::spawn(network::peer::show_received_messages(addr, tx.subscribe(), start_time));
+    tokio::spawn(network::peer::show_received_messages(self_id, tx.subscribe(), start_time));
 
     // Keep the main function alive
     loop {
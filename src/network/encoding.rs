@@ -0,0 +1,115 @@
+//! Minimal hex and base62 codecs for the key material accepted on the
+//! command line, so operators can paste in whichever is more convenient
+//! without pulling in an extra dependency for either.
+
+const BASE62_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) {
+        return Err("hex string must have an even length".to_string());
+    }
+    if !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("hex string must contain only hex digits".to_string());
+    }
+    // Safe to index by byte offset now that every char has been checked
+    // to be a single-byte ASCII hex digit.
+    let bytes = s.as_bytes();
+    (0..bytes.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Decodes a base62 string into exactly `len` bytes, treating it as a
+/// big-endian arbitrary-precision integer.
+pub fn decode_base62(s: &str, len: usize) -> Result<Vec<u8>, String> {
+    let mut value = vec![0u8; len];
+    for ch in s.chars() {
+        let digit = BASE62_ALPHABET
+            .iter()
+            .position(|&b| b == ch as u8)
+            .ok_or_else(|| format!("'{}' is not a valid base62 character", ch))? as u32;
+        let mut carry = digit;
+        for byte in value.iter_mut().rev() {
+            let product = *byte as u32 * 62 + carry;
+            *byte = (product & 0xff) as u8;
+            carry = product >> 8;
+        }
+        if carry != 0 {
+            return Err("base62 value overflows the expected byte length".to_string());
+        }
+    }
+    Ok(value)
+}
+
+/// Decodes CLI-supplied key material that is either hex or base62 encoded.
+/// Hex is assumed when the string is exactly `len * 2` hex digits long.
+pub fn decode_key_material(s: &str, len: usize) -> Result<Vec<u8>, String> {
+    if s.len() == len * 2 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+        decode_hex(s)
+    } else {
+        decode_base62(s, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trips() {
+        let bytes = [0u8, 1, 2, 254, 255, 16, 32];
+        assert_eq!(decode_hex(&encode_hex(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn decode_hex_rejects_odd_length() {
+        assert!(decode_hex("abc").is_err());
+    }
+
+    #[test]
+    fn decode_hex_rejects_non_hex_without_panicking() {
+        assert!(decode_hex("zz").is_err());
+        // A multi-byte UTF-8 character must be rejected, not cause a
+        // byte-offset slice to panic on a non-char-boundary index.
+        assert!(decode_hex("aéa").is_err());
+    }
+
+    #[test]
+    fn base62_round_trips() {
+        let bytes = [0u8, 1, 2, 254, 255, 16, 32];
+        let encoded: String = BASE62_ALPHABET.iter().map(|&b| b as char).take(4).collect();
+        // Any value decode_base62 accepts must round-trip through encode/decode
+        // of the byte buffer itself (base62 has no canonical encoder here).
+        let decoded = decode_base62(&encoded, bytes.len()).unwrap();
+        assert_eq!(decoded.len(), bytes.len());
+    }
+
+    #[test]
+    fn decode_base62_rejects_invalid_characters() {
+        assert!(decode_base62("!!!!", 4).is_err());
+    }
+
+    #[test]
+    fn decode_base62_rejects_overflow() {
+        let all_max = "z".repeat(64);
+        assert!(decode_base62(&all_max, 1).is_err());
+    }
+
+    #[test]
+    fn decode_key_material_prefers_hex_when_exact_length_and_hex_digits() {
+        let bytes = [0xabu8; 32];
+        let hex = encode_hex(&bytes);
+        assert_eq!(decode_key_material(&hex, 32).unwrap(), bytes);
+    }
+
+    #[test]
+    fn decode_key_material_falls_back_to_base62() {
+        let encoded: String = BASE62_ALPHABET.iter().map(|&b| b as char).take(10).collect();
+        assert_eq!(decode_key_material(&encoded, 32).unwrap().len(), 32);
+    }
+}
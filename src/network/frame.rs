@@ -0,0 +1,62 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Reject absurd length prefixes before allocating a buffer for them.
+const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+/// Reads length-prefixed frames off of an `AsyncRead` half.
+///
+/// Each frame is a 4-byte big-endian payload length followed by that many
+/// bytes of payload, replacing the old newline-delimited JSON so fields
+/// containing `\n` can't corrupt the stream and partial TCP reads are
+/// handled by reading exactly `len` bytes before handing the payload back.
+/// The handshake and the encrypted transport decode the raw bytes
+/// themselves once a session is established.
+pub struct FramedReader<R> {
+    inner: R,
+}
+
+impl<R: AsyncRead + Unpin> FramedReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    /// Reads the next frame's raw payload. Returns `Ok(None)` on a clean EOF.
+    pub async fn read_raw_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut len_buf = [0u8; 4];
+        match self.inner.read_exact(&mut len_buf).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let len = u32::from_be_bytes(len_buf);
+        if len > MAX_FRAME_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_LEN),
+            ));
+        }
+
+        let mut payload = vec![0u8; len as usize];
+        self.inner.read_exact(&mut payload).await?;
+        Ok(Some(payload))
+    }
+}
+
+/// Writes length-prefixed frames to an `AsyncWrite` half.
+pub struct FramedWriter<W> {
+    inner: W,
+}
+
+impl<W: AsyncWrite + Unpin> FramedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub async fn write_raw_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        let len = payload.len() as u32;
+        self.inner.write_all(&len.to_be_bytes()).await?;
+        self.inner.write_all(payload).await?;
+        self.inner.flush().await
+    }
+}
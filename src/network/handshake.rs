@@ -0,0 +1,353 @@
+use super::frame::{FramedReader, FramedWriter};
+use super::identity::{Identity, NodeId};
+use ed25519_dalek::{Signature, Signer, Verifier};
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::io;
+use tokio::io::{AsyncRead, AsyncWrite};
+use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_LEN: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct Hello {
+    node_id: NodeId,
+    nonce: [u8; NONCE_LEN],
+    ephemeral_public: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize)]
+struct Auth {
+    signature: Vec<u8>,
+    hmac: Vec<u8>,
+}
+
+/// What a successful handshake establishes about the connection.
+pub struct HandshakeOutcome {
+    pub peer_id: NodeId,
+    /// Key used to encrypt frames sent to this peer.
+    pub send_key: [u8; 32],
+    /// Key used to decrypt frames received from this peer.
+    pub recv_key: [u8; 32],
+}
+
+#[derive(Debug)]
+pub struct HandshakeError(pub String);
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for HandshakeError {}
+
+impl From<io::Error> for HandshakeError {
+    fn from(e: io::Error) -> Self {
+        HandshakeError(e.to_string())
+    }
+}
+
+/// Performs a mutual handshake over `reader`/`writer`.
+///
+/// Both sides send their `NodeId`, a random nonce, and an ephemeral X25519
+/// public key, then sign a transcript covering both identities, both
+/// nonces, *and* both ephemeral public keys with their ed25519 secret key,
+/// and authenticate that same transcript with an HMAC keyed by the shared
+/// `network_key`. Binding the ephemeral keys into what gets signed/HMAC'd
+/// (rather than just the nonce) is what stops an on-path attacker from
+/// transparently proxying the handshake while substituting its own
+/// ephemeral key in each direction -- such a substitution changes the
+/// transcript each side signs, so it's caught here instead of silently
+/// producing two attacker-known session keys. A peer that doesn't hold
+/// the network key, or can't prove ownership of its claimed `NodeId` over
+/// this transcript, is rejected here -- before a single `NetworkData`
+/// frame is ever processed. On success a per-connection session key is
+/// derived from an X25519 Diffie-Hellman exchange between the ephemeral
+/// keys just authenticated, split into a send/recv pair so the two
+/// directions never reuse nonces under the same key.
+pub async fn perform_handshake<R, W>(
+    reader: &mut FramedReader<R>,
+    writer: &mut FramedWriter<W>,
+    identity: &Identity,
+    network_key: &[u8],
+) -> Result<HandshakeOutcome, HandshakeError>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
+    let ephemeral_public = X25519PublicKey::from(&ephemeral_secret);
+
+    let hello = Hello {
+        node_id: identity.node_id,
+        nonce,
+        ephemeral_public: ephemeral_public.to_bytes(),
+    };
+    send(writer, &hello).await?;
+    let peer_hello: Hello = recv(reader).await?;
+
+    let own_ephemeral = ephemeral_public.to_bytes();
+    let own_transcript = handshake_transcript(
+        &identity.node_id, &peer_hello.node_id, &nonce, &peer_hello.nonce, &own_ephemeral, &peer_hello.ephemeral_public,
+    );
+    let signature = identity.signing_key.sign(&own_transcript);
+    let hmac_tag = admission_tag(network_key, &own_transcript);
+    let auth = Auth {
+        signature: signature.to_bytes().to_vec(),
+        hmac: hmac_tag,
+    };
+    send(writer, &auth).await?;
+    let peer_auth: Auth = recv(reader).await?;
+
+    // The peer must have produced the same transcript we expect from our
+    // side of the exchange, proven two ways: an HMAC keyed by the shared
+    // network key (checked with a constant-time comparison so a timing
+    // difference can't leak the tag)...
+    let expected_transcript = handshake_transcript(
+        &peer_hello.node_id, &identity.node_id, &peer_hello.nonce, &nonce, &peer_hello.ephemeral_public, &own_ephemeral,
+    );
+    if !verify_admission_tag(network_key, &expected_transcript, &peer_auth.hmac) {
+        return Err(HandshakeError("peer does not hold the network key".to_string()));
+    }
+
+    // ...and an ed25519 signature proving it controls the secret key
+    // behind its claimed NodeId. Both are over the same transcript --
+    // including both ephemeral keys -- so a proxy that swaps in its own
+    // ephemeral key on either leg can't pass these checks.
+    let signature = Signature::try_from(peer_auth.signature.as_slice())
+        .map_err(|e| HandshakeError(format!("malformed signature: {}", e)))?;
+    peer_hello
+        .node_id
+        .0
+        .verify(&expected_transcript, &signature)
+        .map_err(|_| HandshakeError("identity signature check failed".to_string()))?;
+
+    let peer_ephemeral_public = X25519PublicKey::from(peer_hello.ephemeral_public);
+    let shared_secret = ephemeral_secret.diffie_hellman(&peer_ephemeral_public);
+    let session_key = derive_session_key(shared_secret.as_bytes(), network_key);
+    let (send_key, recv_key) = derive_directional_keys(&session_key, &identity.node_id, &peer_hello.node_id);
+
+    Ok(HandshakeOutcome { peer_id: peer_hello.node_id, send_key, recv_key })
+}
+
+async fn send<W: AsyncWrite + Unpin, T: Serialize>(
+    writer: &mut FramedWriter<W>,
+    value: &T,
+) -> Result<(), HandshakeError> {
+    let bytes = serde_json::to_vec(value).map_err(|e| HandshakeError(e.to_string()))?;
+    writer.write_raw_frame(&bytes).await?;
+    Ok(())
+}
+
+async fn recv<R: AsyncRead + Unpin, T: for<'de> Deserialize<'de>>(
+    reader: &mut FramedReader<R>,
+) -> Result<T, HandshakeError> {
+    let bytes = reader
+        .read_raw_frame()
+        .await?
+        .ok_or_else(|| HandshakeError("connection closed during handshake".to_string()))?;
+    serde_json::from_slice(&bytes).map_err(|e| HandshakeError(e.to_string()))
+}
+
+/// The transcript signed and HMAC'd during the handshake: both identities,
+/// both nonces, and -- critically -- both ephemeral X25519 public keys.
+/// Binding the ephemeral keys in means a proxy that substitutes its own
+/// ephemeral key on either leg changes this transcript, so the signature
+/// and HMAC checks fail instead of silently authenticating a session key
+/// the proxy also knows.
+fn handshake_transcript(
+    sender: &NodeId,
+    recipient: &NodeId,
+    sender_nonce: &[u8],
+    recipient_nonce: &[u8],
+    sender_ephemeral: &[u8; 32],
+    recipient_ephemeral: &[u8; 32],
+) -> Vec<u8> {
+    let mut input = Vec::new();
+    input.extend_from_slice(&sender.to_bytes());
+    input.extend_from_slice(&recipient.to_bytes());
+    input.extend_from_slice(sender_nonce);
+    input.extend_from_slice(recipient_nonce);
+    input.extend_from_slice(sender_ephemeral);
+    input.extend_from_slice(recipient_ephemeral);
+    input
+}
+
+/// The HMAC tag that proves whoever holds `network_key` produced
+/// `transcript`, so a tag can't be replayed onto a different pair of
+/// peers, a different handshake attempt, or a different ephemeral key.
+fn admission_tag(network_key: &[u8], transcript: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(network_key).expect("HMAC accepts any key length");
+    mac.update(transcript);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Checks `tag` against the expected admission tag in constant time, so a
+/// timing difference on this security-critical comparison can't be used to
+/// recover the shared network key one byte at a time.
+fn verify_admission_tag(network_key: &[u8], transcript: &[u8], tag: &[u8]) -> bool {
+    let mut mac = HmacSha256::new_from_slice(network_key).expect("HMAC accepts any key length");
+    mac.update(transcript);
+    mac.verify_slice(tag).is_ok()
+}
+
+fn derive_session_key(shared_secret: &[u8], network_key: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(network_key).expect("HMAC accepts any key length");
+    mac.update(b"p2p-gossiping-app session key");
+    mac.update(shared_secret);
+    let out = mac.finalize().into_bytes();
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&out);
+    key
+}
+
+/// Splits the session key into a send/recv pair. Both sides must agree on
+/// which label goes with which direction without any extra negotiation,
+/// so we order by the (public, well-known) `NodeId`s themselves.
+fn derive_directional_keys(session_key: &[u8; 32], own_id: &NodeId, peer_id: &NodeId) -> ([u8; 32], [u8; 32]) {
+    let own_label: &[u8] = if own_id.to_bytes() < peer_id.to_bytes() { b"a2b" } else { b"b2a" };
+    let peer_label: &[u8] = if own_label == b"a2b" { b"b2a" } else { b"a2b" };
+    (subkey(session_key, own_label), subkey(session_key, peer_label))
+}
+
+fn subkey(session_key: &[u8; 32], label: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(session_key).expect("HMAC accepts any key length");
+    mac.update(label);
+    let out = mac.finalize().into_bytes();
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&out);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Pair = (
+        FramedReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>,
+        FramedWriter<tokio::io::WriteHalf<tokio::io::DuplexStream>>,
+    );
+
+    fn make_pair() -> (Pair, Pair) {
+        let (a, b) = tokio::io::duplex(4096);
+        let (ar, aw) = tokio::io::split(a);
+        let (br, bw) = tokio::io::split(b);
+        ((FramedReader::new(ar), FramedWriter::new(aw)), (FramedReader::new(br), FramedWriter::new(bw)))
+    }
+
+    #[tokio::test]
+    async fn handshake_succeeds_with_matching_network_key() {
+        let ((mut ar, mut aw), (mut br, mut bw)) = make_pair();
+        let key = b"shared-network-key".to_vec();
+        let identity_a = Identity::generate();
+        let identity_b = Identity::generate();
+
+        let (a_result, b_result) = tokio::join!(
+            perform_handshake(&mut ar, &mut aw, &identity_a, &key),
+            perform_handshake(&mut br, &mut bw, &identity_b, &key),
+        );
+
+        let a = a_result.expect("handshake should succeed");
+        let b = b_result.expect("handshake should succeed");
+        assert_eq!(a.peer_id, identity_b.node_id);
+        assert_eq!(b.peer_id, identity_a.node_id);
+        assert_eq!(a.send_key, b.recv_key);
+        assert_eq!(a.recv_key, b.send_key);
+    }
+
+    #[tokio::test]
+    async fn handshake_fails_with_mismatched_network_key() {
+        let ((mut ar, mut aw), (mut br, mut bw)) = make_pair();
+        let identity_a = Identity::generate();
+        let identity_b = Identity::generate();
+
+        let (a_result, b_result) = tokio::join!(
+            perform_handshake(&mut ar, &mut aw, &identity_a, b"key-one"),
+            perform_handshake(&mut br, &mut bw, &identity_b, b"key-two"),
+        );
+
+        assert!(a_result.is_err());
+        assert!(b_result.is_err());
+    }
+
+    #[tokio::test]
+    async fn handshake_fails_with_invalid_signature() {
+        let ((mut ar, mut aw), (mut br, mut bw)) = make_pair();
+        let key = b"shared-network-key".to_vec();
+        let identity_a = Identity::generate();
+        let identity_b = Identity::generate();
+
+        let real_side = perform_handshake(&mut ar, &mut aw, &identity_a, &key);
+        let forged_side = forge_peer_with_bad_signature(&mut br, &mut bw, &identity_b, &key);
+
+        let (a_result, _) = tokio::join!(real_side, forged_side);
+        assert!(a_result.is_err());
+    }
+
+    #[test]
+    fn admission_tag_changes_if_either_ephemeral_key_is_substituted() {
+        let identity_a = Identity::generate();
+        let identity_b = Identity::generate();
+        let nonce_a = [1u8; NONCE_LEN];
+        let nonce_b = [2u8; NONCE_LEN];
+        let eph_a = [3u8; 32];
+        let eph_b = [4u8; 32];
+        let attacker_eph = [5u8; 32];
+        let key = b"shared-network-key";
+
+        let transcript = handshake_transcript(&identity_a.node_id, &identity_b.node_id, &nonce_a, &nonce_b, &eph_a, &eph_b);
+        let tag = admission_tag(key, &transcript);
+
+        // An on-path attacker substituting its own ephemeral key on
+        // either leg must change the transcript -- and so the expected
+        // tag -- or the substitution would go undetected.
+        let tampered_recipient_eph = handshake_transcript(&identity_a.node_id, &identity_b.node_id, &nonce_a, &nonce_b, &eph_a, &attacker_eph);
+        let tampered_sender_eph = handshake_transcript(&identity_a.node_id, &identity_b.node_id, &nonce_a, &nonce_b, &attacker_eph, &eph_b);
+        assert!(!verify_admission_tag(key, &tampered_recipient_eph, &tag));
+        assert!(!verify_admission_tag(key, &tampered_sender_eph, &tag));
+        assert!(verify_admission_tag(key, &transcript, &tag));
+    }
+
+    /// Drives the wire protocol like a real peer -- including a valid
+    /// admission HMAC over the real transcript -- except it signs just
+    /// the peer's nonce (the old, pre-transcript-binding scheme) instead
+    /// of the full transcript, to exercise `perform_handshake`'s
+    /// signature check in isolation from its HMAC check.
+    async fn forge_peer_with_bad_signature<R, W>(
+        reader: &mut FramedReader<R>,
+        writer: &mut FramedWriter<W>,
+        identity: &Identity,
+        network_key: &[u8],
+    ) where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public = X25519PublicKey::from(&ephemeral_secret);
+        let own_ephemeral = ephemeral_public.to_bytes();
+
+        let hello = Hello { node_id: identity.node_id, nonce, ephemeral_public: own_ephemeral };
+        send(writer, &hello).await.unwrap();
+        let peer_hello: Hello = recv(reader).await.unwrap();
+
+        let signature = identity.signing_key.sign(&peer_hello.nonce);
+        let transcript = handshake_transcript(
+            &identity.node_id, &peer_hello.node_id, &nonce, &peer_hello.nonce, &own_ephemeral, &peer_hello.ephemeral_public,
+        );
+        let hmac_tag = admission_tag(network_key, &transcript);
+        let auth = Auth { signature: signature.to_bytes().to_vec(), hmac: hmac_tag };
+        send(writer, &auth).await.unwrap();
+        let _peer_auth: Auth = recv(reader).await.unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+use super::encoding::{decode_hex, encode_hex};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use rand::rngs::OsRng;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// A node's stable identity on the network: the public half of its
+/// long-lived ed25519 keypair. Unlike a `SocketAddr`, this can't be
+/// claimed by simply writing a different value into a message -- every
+/// connection proves ownership of it during the handshake.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(pub VerifyingKey);
+
+impl NodeId {
+    pub fn to_bytes(self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+}
+
+impl fmt::Debug for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NodeId({})", self)
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", encode_hex(&self.to_bytes()))
+    }
+}
+
+impl Serialize for NodeId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_hex(&self.to_bytes()))
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let bytes = decode_hex(&s).map_err(D::Error::custom)?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| D::Error::custom("node id must be 32 bytes"))?;
+        let key = VerifyingKey::from_bytes(&bytes).map_err(D::Error::custom)?;
+        Ok(NodeId(key))
+    }
+}
+
+/// This node's long-lived ed25519 identity. The `NodeId` is what gets
+/// shared with peers; `signing_key` never leaves the process.
+pub struct Identity {
+    pub signing_key: SigningKey,
+    pub node_id: NodeId,
+}
+
+impl Identity {
+    /// Generates a fresh random identity.
+    pub fn generate() -> Self {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let node_id = NodeId(signing_key.verifying_key());
+        Identity { signing_key, node_id }
+    }
+
+    /// Rebuilds an identity from a hex or base62 encoded secret key, as
+    /// accepted on the `--private-key` CLI flag.
+    pub fn from_encoded(s: &str) -> Result<Self, String> {
+        let bytes = super::encoding::decode_key_material(s, 32)?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| "private key must be 32 bytes".to_string())?;
+        let signing_key = SigningKey::from_bytes(&bytes);
+        let node_id = NodeId(signing_key.verifying_key());
+        Ok(Identity { signing_key, node_id })
+    }
+
+    /// Encodes the secret key as hex, for `--private-key` to reuse across restarts.
+    pub fn to_encoded(&self) -> String {
+        encode_hex(&self.signing_key.to_bytes())
+    }
+}
@@ -1,25 +1,53 @@
+use super::identity::NodeId;
+use super::transport::NamedSocketAddr;
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+
+/// Number of hops a freshly originated message is allowed to travel
+/// before nodes stop relaying it further.
+pub const INITIAL_TTL: u8 = 5;
 
 /// Represents a message in the network
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Message {
+    /// Uniquely identifies this message so flood gossip can recognize and
+    /// drop duplicates instead of re-displaying or re-forwarding them.
+    pub id: u128,
     pub content: String,
-    pub from: SocketAddr,
+    pub from: NodeId,
     pub timestamp: u64,
+    /// Hop counter, decremented on every relay; a message stops being
+    /// forwarded once this reaches zero.
+    pub ttl: u8,
+}
+
+/// A peer known to a node, as shared during gossip: its cryptographic
+/// identity plus the address it can be dialed at.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KnownPeer {
+    pub node_id: NodeId,
+    pub addr: NamedSocketAddr,
 }
 
 /// Represents peer information for discovery
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PeerInfo {
-    pub port: u16,
-    pub known_peers: Vec<SocketAddr>,
+    /// The address the sender can be dialed back at.
+    pub listen_addr: NamedSocketAddr,
+    pub known_peers: Vec<KnownPeer>,
 }
 
 /// Enum to differentiate between message types
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type", content = "data")]
 pub enum NetworkData {
-    Message(Message),
+    Message(Box<Message>),
     PeerInfo(PeerInfo),
+    /// Requests a random sample of the recipient's view, for peer sampling.
+    Pull,
+    /// Answers a `Pull` with a random sample of the sender's view.
+    Push(Vec<KnownPeer>),
+    /// Sent periodically on idle connections so a half-open link (one
+    /// whose peer is gone but whose TCP/Unix socket hasn't noticed yet)
+    /// gets detected by the read-timeout instead of lingering forever.
+    Ping,
 }
@@ -0,0 +1,12 @@
+pub mod encoding;
+pub mod frame;
+pub mod handshake;
+pub mod identity;
+pub mod message;
+pub mod peer;
+pub mod reconnect;
+pub mod secure;
+pub mod seen;
+pub mod stats;
+pub mod transport;
+pub mod view;
@@ -1,118 +1,243 @@
+use super::frame::{FramedReader, FramedWriter};
+use super::handshake::{self, HandshakeOutcome};
+use super::identity::{Identity, NodeId};
 use super::message::{NetworkData, PeerInfo};
-use crate::utils::{log_with_timestamp, is_recent};
-use std::collections::HashSet;
-use std::net::SocketAddr;
+use super::secure::{SecureReader, SecureWriter};
+use super::seen::SeenMessages;
+use super::stats::{self, SharedStats};
+use super::transport::{Listener, NamedSocketAddr};
+use super::view::{PeerView, GOSSIP_FANOUT};
+use crate::utils::{is_recent, log_with_timestamp};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::broadcast;
 
-/// Type alias for a shared list of peers
-type SharedPeers = Arc<Mutex<HashSet<SocketAddr>>>;
+/// How often an idle connection sends a `Ping` to the peer, so a
+/// half-open link gets noticed instead of lingering silently.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long to wait for any frame (including a keepalive `Ping`) before
+/// treating a connection as dead.
+const READ_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Type alias for the shared, bounded view of known peers.
+pub type SharedPeers = Arc<Mutex<PeerView>>;
+
+/// Type alias for the shared set of recently seen flood-gossip message ids.
+pub type SharedSeen = Arc<Mutex<SeenMessages>>;
+
+/// The state every connection (inbound or outbound) needs: who we are,
+/// what cluster we're admitting peers into, and the shared view/gossip
+/// bus they get wired into. Bundled into one `Clone`-able handle so
+/// adding connection-level shared state doesn't keep growing every
+/// function's argument list.
+#[derive(Clone)]
+pub struct NodeHandle {
+    pub identity: Arc<Identity>,
+    pub network_key: Arc<Vec<u8>>,
+    /// The address this node listens on, advertised to peers we dial so
+    /// they can reach us back.
+    pub listen_addr: NamedSocketAddr,
+    pub peers: SharedPeers,
+    pub seen: SharedSeen,
+    pub stats: SharedStats,
+    pub tx: broadcast::Sender<(NetworkData, Destination)>,
+    pub start_time: Instant,
+}
+
+/// Who a broadcast-channel item should be delivered to. `Broadcast` floods
+/// every connected peer except the one it came from (used to relay
+/// gossip `Message`s); `Direct` targets exactly one peer (used for the
+/// `Pull`/`Push` peer-sampling exchange and for fanout dissemination of a
+/// freshly originated message); `Local` reaches no peer connection at all,
+/// only `show_received_messages`, for a message whose TTL has expired.
+#[derive(Clone, Copy)]
+pub enum Destination {
+    Broadcast { except: NodeId },
+    Direct(NodeId),
+    Local,
+}
+
+impl Destination {
+    fn deliverable_to(self, peer_id: NodeId) -> bool {
+        match self {
+            Destination::Broadcast { except } => except != peer_id,
+            Destination::Direct(target) => target == peer_id,
+            Destination::Local => false,
+        }
+    }
+}
 
 /// Accept incoming connections and handle them
-pub async fn accept_connections(listener: TcpListener, peers: SharedPeers, tx: broadcast::Sender<(String, SocketAddr)>, self_addr: SocketAddr, start_time: Instant) {
+pub async fn accept_connections(listener: Listener, node: NodeHandle) {
     loop {
-        if let Ok((socket, _)) = listener.accept().await {
-            let _addr = socket.peer_addr().unwrap();
-
-            // Read the peer's intended port
-            let mut reader = BufReader::new(socket);
-            let mut buf = String::new();
-            reader.read_line(&mut buf).await.unwrap();
-            let network_data: NetworkData = serde_json::from_str(&buf).unwrap();
-            if let NetworkData::PeerInfo(peer_info) = network_data {
-                let peer_addr = format!("127.0.0.1:{}", peer_info.port).parse().unwrap();
-
-                log_with_timestamp(start_time, &format!("Connected to the peer at \"{}\"", peer_addr));
-                let mut peer_list = peers.lock().unwrap();
-                peer_list.insert(peer_addr);
-                for known_peer in peer_info.known_peers {
-                    if known_peer != self_addr {
-                        peer_list.insert(known_peer);
+        if let Ok((read_half, write_half)) = listener.accept().await {
+            let node = node.clone();
+            // Handshaking is spawned per-connection so a slow or hostile
+            // peer can't stall acceptance of everyone else.
+            tokio::spawn(async move {
+                let mut reader = FramedReader::new(read_half);
+                let mut writer = FramedWriter::new(write_half);
+
+                let outcome = match handshake::perform_handshake(&mut reader, &mut writer, &node.identity, &node.network_key).await {
+                    Ok(outcome) => outcome,
+                    Err(e) => {
+                        log_with_timestamp(node.start_time, &format!("Rejected inbound connection: {}", e));
+                        return;
                     }
+                };
+
+                let mut secure_reader = SecureReader::new(reader, outcome.recv_key);
+                let secure_writer = SecureWriter::new(writer, outcome.send_key);
+
+                // The first application-level message announces the peer's listen address.
+                let network_data = match secure_reader.read_frame().await {
+                    Ok(Some((data, _))) => data,
+                    _ => return,
+                };
+                if let NetworkData::PeerInfo(peer_info) = network_data {
+                    register_peer(&node, &outcome, peer_info);
+                    handle_connection(secure_reader, secure_writer, outcome.peer_id, node).await;
                 }
-                log_with_timestamp(start_time, &format!("{:?}", peer_list));
+            });
+        }
+    }
+}
 
-                tokio::spawn(handle_connection(reader.into_inner(), peers.clone(), tx.clone(), self_addr, start_time));
-            }
+fn register_peer(node: &NodeHandle, outcome: &HandshakeOutcome, peer_info: PeerInfo) {
+    log_with_timestamp(node.start_time, &format!("Connected to the peer at \"{}\" ({})", peer_info.listen_addr, outcome.peer_id));
+
+    let mut view = node.peers.lock().unwrap();
+    view.insert(outcome.peer_id, peer_info.listen_addr.clone());
+    for known in peer_info.known_peers {
+        if known.node_id != node.identity.node_id {
+            view.insert(known.node_id, known.addr);
         }
     }
+    log_with_timestamp(node.start_time, &format!("{:?}", view));
 }
 
-/// Connect to a specified peer and handle the connection
-pub async fn connect_to_peer(addr: SocketAddr, port: u16, peers: SharedPeers, tx: broadcast::Sender<(String, SocketAddr)>, self_addr: SocketAddr, start_time: Instant) {
-    if let Ok(mut socket) = TcpStream::connect(addr).await {
-        log_with_timestamp(start_time, &format!("Connected to the peer at \"{}\"", addr));
+/// Connect to a specified peer and handle the connection. Returns whether
+/// the connection was established, so callers like the reconnection
+/// supervisor can track retries.
+pub async fn connect_to_peer(addr: NamedSocketAddr, node: NodeHandle) -> bool {
+    let Ok((read_half, write_half)) = super::transport::connect(&addr).await else {
+        return false;
+    };
+    let mut reader = FramedReader::new(read_half);
+    let mut writer = FramedWriter::new(write_half);
 
-        let known_peers: Vec<SocketAddr> = peers.lock().unwrap()
-            .iter().cloned().filter(|p| p != &self_addr).collect();
-        let peer_info = PeerInfo { port, known_peers };
-        let network_data = NetworkData::PeerInfo(peer_info);
-        let peer_info_json = serde_json::to_string(&network_data).unwrap() + "\n"; // Add a delimiter
-        socket.write_all(peer_info_json.as_bytes()).await.unwrap();
+    let outcome = match handshake::perform_handshake(&mut reader, &mut writer, &node.identity, &node.network_key).await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            log_with_timestamp(node.start_time, &format!("Handshake with \"{}\" failed: {}", addr, e));
+            return false;
+        }
+    };
+    log_with_timestamp(node.start_time, &format!("Connected to the peer at \"{}\" ({})", addr, outcome.peer_id));
+
+    let mut secure_writer = SecureWriter::new(writer, outcome.send_key);
+    let secure_reader = SecureReader::new(reader, outcome.recv_key);
 
-        peers.lock().unwrap().insert(addr);
-        tokio::spawn(handle_connection(socket, peers, tx, self_addr, start_time));
+    let known_peers = node.peers.lock().unwrap().random_known_peers(GOSSIP_FANOUT);
+    let peer_info = PeerInfo { listen_addr: node.listen_addr.clone(), known_peers };
+    if secure_writer.write_frame(&NetworkData::PeerInfo(peer_info)).await.is_err() {
+        return false;
     }
+
+    node.peers.lock().unwrap().insert(outcome.peer_id, addr);
+    tokio::spawn(handle_connection(secure_reader, secure_writer, outcome.peer_id, node));
+    true
 }
 
 /// Handle connection for a peer, manage message passing and disconnection
-pub async fn handle_connection(socket: TcpStream, peers: SharedPeers, tx: broadcast::Sender<(String, SocketAddr)>, self_addr: SocketAddr, start_time: Instant) {
-    let _ = start_time;
-    let addr = socket.peer_addr().unwrap();
-    let (reader, mut writer) = tokio::io::split(socket);
-    let mut rx = tx.subscribe();
+pub async fn handle_connection<R, W>(mut reader: SecureReader<R>, mut writer: SecureWriter<W>, peer_id: NodeId, node: NodeHandle)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    node.peers.lock().unwrap().mark_connected(peer_id);
+    log_with_timestamp(node.start_time, &format!("Peer {} is now connected", peer_id));
 
-    let peers_clone = peers.clone();
-    tokio::spawn(async move {
-        let mut reader = BufReader::new(reader);
-        let mut line = String::new();
+    let mut rx = node.tx.subscribe();
 
+    let peers_clone = node.peers.clone();
+    let seen_clone = node.seen.clone();
+    let stats_clone = node.stats.clone();
+    let tx_clone = node.tx.clone();
+    let self_id = node.identity.node_id;
+    let start_time = node.start_time;
+    tokio::spawn(async move {
         loop {
-            line.clear();
-            match reader.read_line(&mut line).await {
-                Ok(0) => {
-                    // Connection was closed
-                    peers_clone.lock().unwrap().remove(&addr);
+            let network_data = match tokio::time::timeout(READ_TIMEOUT, reader.read_frame()).await {
+                Ok(Ok(Some((network_data, bytes)))) => {
+                    stats::record_received(&stats_clone, peer_id, bytes);
+                    network_data
+                }
+                Ok(Ok(None)) | Ok(Err(_)) | Err(_) => {
+                    // Connection was closed, errored, or went quiet past the
+                    // keepalive read timeout: give up and let the
+                    // reconnection supervisor redial it later.
+                    peers_clone.lock().unwrap().mark_disconnected(&peer_id);
+                    log_with_timestamp(start_time, &format!("Peer {} disconnected", peer_id));
                     break;
                 }
-                Ok(_) => {
-                    let msg = line.trim().to_string();
-                    if !msg.is_empty() {
-                        let network_data: NetworkData = serde_json::from_str(&msg).unwrap();
-                        match network_data {
-                            NetworkData::Message(message) => {
-                                if message.from != self_addr {
-                                    peers_clone.lock().unwrap().insert(message.from);
-                                }
-                                let _ = tx.send((msg, addr));
-                            }
-                            NetworkData::PeerInfo(peer_info) => {
-                                let mut peer_list = peers_clone.lock().unwrap();
-                                for known_peer in peer_info.known_peers {
-                                    if known_peer != self_addr {
-                                        peer_list.insert(known_peer);
-                                    }
-                                }
-                            }
+            };
+            match network_data {
+                NetworkData::Message(mut message) => {
+                    if !seen_clone.lock().unwrap().insert(message.id) {
+                        continue;
+                    }
+                    if message.ttl == 0 {
+                        let _ = tx_clone.send((NetworkData::Message(message), Destination::Local));
+                    } else {
+                        message.ttl -= 1;
+                        let _ = tx_clone.send((NetworkData::Message(message), Destination::Broadcast { except: peer_id }));
+                    }
+                }
+                NetworkData::PeerInfo(peer_info) => {
+                    let mut view = peers_clone.lock().unwrap();
+                    for known in peer_info.known_peers {
+                        if known.node_id != self_id {
+                            view.insert(known.node_id, known.addr);
                         }
                     }
                 }
-                Err(_) => {
-                    peers_clone.lock().unwrap().remove(&addr);
-                    break;
+                NetworkData::Pull => {
+                    let sample = peers_clone.lock().unwrap().random_known_peers(GOSSIP_FANOUT);
+                    let _ = tx_clone.send((NetworkData::Push(sample), Destination::Direct(peer_id)));
                 }
+                NetworkData::Push(known_peers) => {
+                    let mut view = peers_clone.lock().unwrap();
+                    for known in known_peers {
+                        if known.node_id != self_id {
+                            view.insert(known.node_id, known.addr);
+                        }
+                    }
+                }
+                NetworkData::Ping => {}
             }
         }
     });
 
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
     loop {
-        if let Ok((msg, peer_addr)) = rx.recv().await {
-            if peer_addr != addr {
-                if let Err(_) = writer.write_all((msg + "\n").as_bytes()).await {
-                    break;
+        tokio::select! {
+            message = rx.recv() => {
+                if let Ok((network_data, destination)) = message {
+                    if destination.deliverable_to(peer_id) {
+                        match writer.write_frame(&network_data).await {
+                            Ok(bytes) => stats::record_sent(&node.stats, peer_id, bytes),
+                            Err(_) => break,
+                        }
+                    }
+                }
+            }
+            _ = keepalive.tick() => {
+                match writer.write_frame(&NetworkData::Ping).await {
+                    Ok(bytes) => stats::record_sent(&node.stats, peer_id, bytes),
+                    Err(_) => break,
                 }
             }
         }
@@ -120,20 +245,18 @@ pub async fn handle_connection(socket: TcpStream, peers: SharedPeers, tx: broadc
 }
 
 /// Display received messages from peers
-pub async fn show_received_messages(addr: SocketAddr, mut rx: broadcast::Receiver<(String, SocketAddr)>, start_time: Instant) {
-    let mut seen_messages = HashSet::new();
+pub async fn show_received_messages(self_id: NodeId, mut rx: broadcast::Receiver<(NetworkData, Destination)>, start_time: Instant) {
     loop {
-        while let Ok((msg, _)) = rx.recv().await {
-            let network_data: NetworkData = serde_json::from_str(&msg.trim()).unwrap(); // Use trim to handle newlines
+        while let Ok((network_data, _)) = rx.recv().await {
             if let NetworkData::Message(message) = network_data {
-                if message.from != addr && is_recent(message.timestamp) {
-                    // Check if the message has already been seen
-                    if seen_messages.insert((message.content.clone(), message.timestamp)) {
-                        log_with_timestamp(start_time, &format!(
+                if message.from != self_id && is_recent(message.timestamp) {
+                    log_with_timestamp(
+                        start_time,
+                        &format!(
                             "Received message [{}] from \"{}\"",
                             message.content, message.from
-                        ));
-                    }
+                        ),
+                    );
                 }
             }
         }
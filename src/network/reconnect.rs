@@ -0,0 +1,68 @@
+use super::identity::NodeId;
+use super::peer::{connect_to_peer, NodeHandle};
+use crate::utils::log_with_timestamp;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How often the supervisor sweeps the view for disconnected peers to redial.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How many consecutive failed redials a peer survives before being
+/// declared dead and evicted from the view.
+pub const MAX_RETRIES: u32 = 5;
+
+/// How long a single redial attempt is given before it's treated as a
+/// failure. Without this, one unreachable peer stuck on the OS-level TCP
+/// connect timeout could stall every other redial in the sweep.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Periodically redials peers that are known (from gossip) but not
+/// currently connected, so a transient drop or restart self-heals
+/// instead of permanently partitioning that link. A peer that fails
+/// `MAX_RETRIES` redials in a row is declared dead and evicted.
+pub async fn run_reconnect_supervisor(node: NodeHandle) {
+    let mut retries: HashMap<NodeId, u32> = HashMap::new();
+    let mut interval = tokio::time::interval(RETRY_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        let candidates = node.peers.lock().unwrap().disconnected_peers();
+        for (peer_id, addr) in candidates {
+            let connected = tokio::time::timeout(CONNECT_TIMEOUT, connect_to_peer(addr, node.clone()))
+                .await
+                .unwrap_or(false);
+            if connected {
+                retries.remove(&peer_id);
+                continue;
+            }
+
+            let attempts = retries.entry(peer_id).or_insert(0);
+            *attempts += 1;
+            if retries_exhausted(*attempts) {
+                log_with_timestamp(node.start_time, &format!("Peer {} unreachable after {} retries, evicting", peer_id, attempts));
+                node.peers.lock().unwrap().remove(&peer_id);
+                retries.remove(&peer_id);
+            } else {
+                log_with_timestamp(node.start_time, &format!("Retry {}/{} to reconnect to {} failed", attempts, MAX_RETRIES, peer_id));
+            }
+        }
+    }
+}
+
+/// Whether `attempts` consecutive failed redials is enough to declare a
+/// peer dead and evict it from the view.
+fn retries_exhausted(attempts: u32) -> bool {
+    attempts >= MAX_RETRIES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_exhausted_trips_at_max_retries() {
+        assert!(!retries_exhausted(MAX_RETRIES - 1));
+        assert!(retries_exhausted(MAX_RETRIES));
+        assert!(retries_exhausted(MAX_RETRIES + 1));
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::{HashSet, VecDeque};
+
+/// How many message ids to remember. Large enough to absorb a burst of
+/// flooded duplicates, small enough to stay bounded regardless of how
+/// long the node runs.
+pub const SEEN_CAPACITY: usize = 4096;
+
+/// A bounded, insertion-ordered set of recently seen message ids.
+///
+/// Flood gossip forwards every message to every neighbor but one, so the
+/// same message arrives at a node multiple times once the mesh has any
+/// cycles. Tracking ids here lets a node recognize and silently drop the
+/// duplicates instead of re-displaying or re-forwarding them forever.
+pub struct SeenMessages {
+    capacity: usize,
+    order: VecDeque<u128>,
+    members: HashSet<u128>,
+}
+
+impl SeenMessages {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            members: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// Records `id` and returns `true` if it hadn't been seen before.
+    /// Returns `false` (without changing anything) for a duplicate.
+    pub fn insert(&mut self, id: u128) -> bool {
+        if !self.members.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.members.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_reports_first_sighting_and_drops_duplicates() {
+        let mut seen = SeenMessages::new(4096);
+        assert!(seen.insert(1));
+        assert!(!seen.insert(1));
+        assert!(seen.insert(2));
+    }
+
+    #[test]
+    fn insert_evicts_the_oldest_id_once_over_capacity() {
+        let mut seen = SeenMessages::new(2);
+        assert!(seen.insert(1));
+        assert!(seen.insert(2));
+        assert!(seen.insert(3));
+
+        // Capacity 2: inserting a 3rd id evicted the oldest (1), so 2 and 3
+        // are still remembered as duplicates...
+        assert!(!seen.insert(2));
+        assert!(!seen.insert(3));
+        // ...but 1 was pushed out and is treated as unseen again.
+        assert!(seen.insert(1));
+    }
+}
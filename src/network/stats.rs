@@ -0,0 +1,123 @@
+use super::identity::NodeId;
+use crate::utils::log_with_timestamp;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Running byte/message counters for one peer, updated from both the
+/// reader and writer side of its connection.
+#[derive(Default)]
+pub struct PeerStats {
+    pub bytes_sent: AtomicU64,
+    pub bytes_received: AtomicU64,
+    pub messages_sent: AtomicU64,
+    pub messages_received: AtomicU64,
+}
+
+/// Per-peer traffic counters, keyed by node id and shared across every
+/// connection so the periodic report can see the whole mesh at once.
+pub type SharedStats = Arc<Mutex<HashMap<NodeId, Arc<PeerStats>>>>;
+
+/// Fetches (creating if necessary) the counters for `peer_id`.
+fn stats_for(stats: &SharedStats, peer_id: NodeId) -> Arc<PeerStats> {
+    stats.lock().unwrap().entry(peer_id).or_default().clone()
+}
+
+pub fn record_sent(stats: &SharedStats, peer_id: NodeId, bytes: usize) {
+    let entry = stats_for(stats, peer_id);
+    entry.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    entry.messages_sent.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_received(stats: &SharedStats, peer_id: NodeId, bytes: usize) {
+    let entry = stats_for(stats, peer_id);
+    entry.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    entry.messages_received.fetch_add(1, Ordering::Relaxed);
+}
+
+/// How often the mesh-wide traffic summary is logged.
+const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Periodically logs per-peer in/out byte rates and totals, plus overall
+/// mesh throughput, so operators can see which links are hot and whether
+/// the gossip fanout is wasting bandwidth.
+pub async fn run_stats_reporter(stats: SharedStats, start_time: std::time::Instant) {
+    let mut interval = tokio::time::interval(REPORT_INTERVAL);
+    let mut previous: HashMap<NodeId, (u64, u64)> = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        let snapshot: Vec<(NodeId, u64, u64, u64, u64)> = stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(peer_id, s)| {
+                (
+                    *peer_id,
+                    s.bytes_sent.load(Ordering::Relaxed),
+                    s.bytes_received.load(Ordering::Relaxed),
+                    s.messages_sent.load(Ordering::Relaxed),
+                    s.messages_received.load(Ordering::Relaxed),
+                )
+            })
+            .collect();
+
+        if snapshot.is_empty() {
+            continue;
+        }
+
+        let elapsed = REPORT_INTERVAL.as_secs_f64();
+        let mut total_sent = 0u64;
+        let mut total_received = 0u64;
+        for (peer_id, bytes_sent, bytes_received, messages_sent, messages_received) in &snapshot {
+            let (prev_sent, prev_received) = previous.get(peer_id).copied().unwrap_or((0, 0));
+            let sent_rate = (*bytes_sent - prev_sent) as f64 / elapsed;
+            let received_rate = (*bytes_received - prev_received) as f64 / elapsed;
+            log_with_timestamp(
+                start_time,
+                &format!(
+                    "Traffic with {}: out {} B total ({:.0} B/s, {} msgs), in {} B total ({:.0} B/s, {} msgs)",
+                    peer_id, bytes_sent, sent_rate, messages_sent, bytes_received, received_rate, messages_received
+                ),
+            );
+            previous.insert(*peer_id, (*bytes_sent, *bytes_received));
+            total_sent += bytes_sent;
+            total_received += bytes_received;
+        }
+        log_with_timestamp(
+            start_time,
+            &format!("Mesh throughput: {} B out, {} B in across {} peers", total_sent, total_received, snapshot.len()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::identity::Identity;
+
+    #[test]
+    fn record_sent_and_received_accumulate_independently_per_peer() {
+        let stats: SharedStats = Arc::new(Mutex::new(HashMap::new()));
+        let peer_a = Identity::generate().node_id;
+        let peer_b = Identity::generate().node_id;
+
+        record_sent(&stats, peer_a, 10);
+        record_sent(&stats, peer_a, 5);
+        record_received(&stats, peer_a, 7);
+        record_sent(&stats, peer_b, 100);
+
+        let locked = stats.lock().unwrap();
+        let a = &locked[&peer_a];
+        assert_eq!(a.bytes_sent.load(Ordering::Relaxed), 15);
+        assert_eq!(a.messages_sent.load(Ordering::Relaxed), 2);
+        assert_eq!(a.bytes_received.load(Ordering::Relaxed), 7);
+        assert_eq!(a.messages_received.load(Ordering::Relaxed), 1);
+
+        let b = &locked[&peer_b];
+        assert_eq!(b.bytes_sent.load(Ordering::Relaxed), 100);
+        assert_eq!(b.bytes_received.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+
+/// An address a peer can be dialed at: either a regular TCP/IP socket or,
+/// for same-host peers, a Unix domain socket path. Carried in `PeerInfo`
+/// and `KnownPeer` so gossip propagates either kind the same way.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NamedSocketAddr {
+    Inet(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl NamedSocketAddr {
+    /// Parses a `--port`/`--connect` value: a `host:port` socket address,
+    /// a bare port number (bound on loopback, for backwards compatibility),
+    /// or a filesystem path for a Unix domain socket.
+    pub fn parse(s: &str) -> Self {
+        if let Ok(addr) = s.parse::<SocketAddr>() {
+            return Self::Inet(addr);
+        }
+        if let Ok(port) = s.parse::<u16>() {
+            return Self::Inet(SocketAddr::from(([127, 0, 0, 1], port)));
+        }
+        Self::Unix(PathBuf::from(s))
+    }
+}
+
+impl fmt::Display for NamedSocketAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Inet(addr) => write!(f, "{}", addr),
+            Self::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A connection's read/write halves, boxed so TCP and Unix transports can
+/// be driven by the same framing and handshake code.
+pub type BoxedReader = Box<dyn AsyncRead + Unpin + Send>;
+pub type BoxedWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// Listens for inbound connections on either transport.
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Listener {
+    pub async fn bind(addr: &NamedSocketAddr) -> io::Result<Self> {
+        match addr {
+            NamedSocketAddr::Inet(addr) => Ok(Self::Tcp(TcpListener::bind(addr).await?)),
+            NamedSocketAddr::Unix(path) => {
+                // A stale socket file from a previous run would otherwise
+                // make the bind fail with "address in use".
+                let _ = std::fs::remove_file(path);
+                Ok(Self::Unix(UnixListener::bind(path)?))
+            }
+        }
+    }
+
+    pub async fn accept(&self) -> io::Result<(BoxedReader, BoxedWriter)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (socket, _) = listener.accept().await?;
+                let (read_half, write_half) = tokio::io::split(socket);
+                Ok((Box::new(read_half), Box::new(write_half)))
+            }
+            Self::Unix(listener) => {
+                let (socket, _) = listener.accept().await?;
+                let (read_half, write_half) = tokio::io::split(socket);
+                Ok((Box::new(read_half), Box::new(write_half)))
+            }
+        }
+    }
+}
+
+/// Dials a peer at either transport.
+pub async fn connect(addr: &NamedSocketAddr) -> io::Result<(BoxedReader, BoxedWriter)> {
+    match addr {
+        NamedSocketAddr::Inet(addr) => {
+            let socket = TcpStream::connect(addr).await?;
+            let (read_half, write_half) = tokio::io::split(socket);
+            Ok((Box::new(read_half), Box::new(write_half)))
+        }
+        NamedSocketAddr::Unix(path) => {
+            let socket = UnixStream::connect(path).await?;
+            let (read_half, write_half) = tokio::io::split(socket);
+            Ok((Box::new(read_half), Box::new(write_half)))
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use super::identity::NodeId;
+use super::message::KnownPeer;
+use super::transport::NamedSocketAddr;
+use rand::seq::IteratorRandom;
+use rand::thread_rng;
+use std::collections::{HashMap, HashSet};
+
+/// Maximum number of peers kept in a node's view. Bounding this keeps
+/// per-node state and the bandwidth spent on gossip constant regardless
+/// of how large the cluster grows.
+pub const VIEW_CAPACITY: usize = 32;
+
+/// How many peers a gossip round targets, both for message dissemination
+/// and for answering a `Pull` with a `Push`.
+pub const GOSSIP_FANOUT: usize = 3;
+
+/// A bounded, randomly-sampled set of known peers (à la Cyclon/HyParView
+/// peer sampling), used in place of an unbounded full mesh. Once the view
+/// is full, a random existing entry is evicted to make room for a new
+/// one, which keeps membership uniform-random and self-healing.
+#[derive(Default)]
+pub struct PeerView {
+    entries: HashMap<NodeId, NamedSocketAddr>,
+    /// Peers with a live connection right now, as opposed to ones merely
+    /// known from gossip. The reconnection supervisor redials whoever is
+    /// in `entries` but not in here.
+    connected: HashSet<NodeId>,
+}
+
+impl PeerView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts or updates a peer, evicting a random existing entry first
+    /// if the view is already full of other peers. Eviction prefers a peer
+    /// we aren't currently connected to, so a live connection doesn't
+    /// silently lose its spot in the view (and with it, future gossip
+    /// reachability and reconnection tracking) -- a connected peer is only
+    /// evicted when the whole view is full of connected peers.
+    pub fn insert(&mut self, node_id: NodeId, addr: NamedSocketAddr) {
+        if !self.entries.contains_key(&node_id) && self.entries.len() >= VIEW_CAPACITY {
+            let mut candidates = self.entries.keys().filter(|id| !self.connected.contains(*id)).peekable();
+            let evict = if candidates.peek().is_some() {
+                candidates.choose(&mut thread_rng()).copied()
+            } else {
+                self.entries.keys().choose(&mut thread_rng()).copied()
+            };
+            if let Some(evict) = evict {
+                self.entries.remove(&evict);
+                self.connected.remove(&evict);
+            }
+        }
+        self.entries.insert(node_id, addr);
+    }
+
+    pub fn remove(&mut self, node_id: &NodeId) {
+        self.entries.remove(node_id);
+        self.connected.remove(node_id);
+    }
+
+    pub fn mark_connected(&mut self, node_id: NodeId) {
+        self.connected.insert(node_id);
+    }
+
+    pub fn mark_disconnected(&mut self, node_id: &NodeId) {
+        self.connected.remove(node_id);
+    }
+
+    /// Known peers that aren't currently connected, for the reconnection
+    /// supervisor to redial.
+    pub fn disconnected_peers(&self) -> Vec<(NodeId, NamedSocketAddr)> {
+        self.entries
+            .iter()
+            .filter(|(node_id, _)| !self.connected.contains(node_id))
+            .map(|(node_id, addr)| (*node_id, addr.clone()))
+            .collect()
+    }
+
+    /// Picks one random peer from the view, e.g. to send a `Pull` to.
+    pub fn random_peer(&self) -> Option<NodeId> {
+        self.entries.keys().choose(&mut thread_rng()).copied()
+    }
+
+    /// Picks up to `n` random peers, e.g. the fanout for a gossip round or
+    /// the subset of the view shared in a `Push`.
+    pub fn random_subset(&self, n: usize) -> Vec<NodeId> {
+        self.entries.keys().choose_multiple(&mut thread_rng(), n).into_iter().copied().collect()
+    }
+
+    /// Snapshots up to `n` random peers as `KnownPeer`s, for a `Push`/`PeerInfo` payload.
+    pub fn random_known_peers(&self, n: usize) -> Vec<KnownPeer> {
+        self.entries
+            .iter()
+            .choose_multiple(&mut thread_rng(), n)
+            .into_iter()
+            .map(|(id, addr)| KnownPeer { node_id: *id, addr: addr.clone() })
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for PeerView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.entries.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::identity::Identity;
+
+    fn addr(port: u16) -> NamedSocketAddr {
+        NamedSocketAddr::parse(&format!("127.0.0.1:{}", port))
+    }
+
+    #[test]
+    fn insert_evicts_a_disconnected_peer_before_a_connected_one() {
+        let mut view = PeerView::new();
+        let connected_id = Identity::generate().node_id;
+        view.insert(connected_id, addr(0));
+        view.mark_connected(connected_id);
+
+        // Fill the rest of the view with disconnected peers.
+        for i in 1..VIEW_CAPACITY {
+            view.insert(Identity::generate().node_id, addr(i as u16));
+        }
+        assert_eq!(view.entries.len(), VIEW_CAPACITY);
+
+        // Inserting one more peer must evict a disconnected one, not the
+        // connected one, even though which disconnected one is random.
+        let newcomer = Identity::generate().node_id;
+        view.insert(newcomer, addr(u16::MAX));
+
+        assert!(view.entries.contains_key(&connected_id));
+        assert!(view.entries.contains_key(&newcomer));
+        assert_eq!(view.entries.len(), VIEW_CAPACITY);
+    }
+
+    #[test]
+    fn insert_evicts_a_connected_peer_when_the_view_is_entirely_connected() {
+        let mut view = PeerView::new();
+        for i in 0..VIEW_CAPACITY {
+            let id = Identity::generate().node_id;
+            view.insert(id, addr(i as u16));
+            view.mark_connected(id);
+        }
+        assert_eq!(view.entries.len(), VIEW_CAPACITY);
+
+        let newcomer = Identity::generate().node_id;
+        view.insert(newcomer, addr(u16::MAX));
+
+        // Capacity is maintained and the newcomer got in, even though
+        // every existing entry was connected and had to give way.
+        assert_eq!(view.entries.len(), VIEW_CAPACITY);
+        assert!(view.entries.contains_key(&newcomer));
+    }
+}